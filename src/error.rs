@@ -7,6 +7,7 @@ pub enum NormalizeError {
     InternalError,
     UrlEncodeError,
     RegexParseError(String),
+    HostParseError(String),
 }
 
 impl Display for NormalizeError {
@@ -16,6 +17,7 @@ impl Display for NormalizeError {
             NormalizeError::InternalError => write!(f, "Internal error."),
             NormalizeError::UrlEncodeError => write!(f, "Url encode error."),
             NormalizeError::RegexParseError(regex) => write!(f, "Regex parse error:{}", regex),
+            NormalizeError::HostParseError(host) => write!(f, "Host parse error:{}", host),
         }
     }
 }
@@ -20,50 +20,337 @@
 //! }
 //! ```
 
-use std::collections::BTreeMap;
-
 use regex::Regex;
-use url::Url;
-use urlencoding::{decode, encode};
+use url::{Host, Url};
+use urlencoding::decode;
 
 use crate::error::NormalizeError;
 
+/// Component-specific percent-encode sets, mirroring rust-url's `AsciiSet`
+/// design: each URL component only escapes the characters that are actually
+/// reserved for that component.
+enum EncodeSet {
+    Path,
+    Query,
+    Fragment,
+    Userinfo,
+}
+
+impl EncodeSet {
+    fn should_escape(&self, byte: u8) -> bool {
+        if byte < 0x20 || byte == 0x7f || byte >= 0x80 {
+            return true;
+        }
+        match self {
+            EncodeSet::Path => matches!(byte, b' ' | b'"' | b'<' | b'>' | b'`' | b'#' | b'?' | b'{' | b'}'),
+            EncodeSet::Query => matches!(byte, b' ' | b'"' | b'#' | b'<' | b'>' | b'&' | b'='),
+            EncodeSet::Fragment => matches!(byte, b' ' | b'"' | b'<' | b'>' | b'`'),
+            EncodeSet::Userinfo => matches!(byte, b' ' | b'"' | b'<' | b'>' | b'`' | b'#' | b'?' | b'{' | b'}'
+                | b'/' | b':' | b';' | b'=' | b'@' | b'[' | b'\\' | b']' | b'^' | b'|'),
+        }
+    }
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Applies RFC 3986 §6.2.2 encoding normalization to a single URL component:
+/// already-percent-encoded unreserved characters are decoded back to their
+/// literal form, other `%XX` triplets keep their hex digits uppercased, and
+/// any not-yet-encoded character required by `set` is percent-encoded.
+fn normalize_component(input: &str, set: &EncodeSet) -> String {
+    let bytes = input.as_bytes();
+    let mut out = String::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                let decoded = (hi << 4) | lo;
+                if is_unreserved(decoded) {
+                    out.push(decoded as char);
+                } else {
+                    out.push('%');
+                    out.push(bytes[i + 1].to_ascii_uppercase() as char);
+                    out.push(bytes[i + 2].to_ascii_uppercase() as char);
+                }
+                i += 3;
+                continue;
+            }
+        }
+        if b == b'%' {
+            out.push_str("%25");
+        } else if set.should_escape(b) {
+            out.push_str(&format!("%{:02X}", b));
+        } else {
+            out.push(b as char);
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Trailing-slash normalization policy, borrowing the "trailing" vs.
+/// "nontrailing" distinction from Rocket's conservative URI normalization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Keep the trailing slash as-is: `/foo/` stays `/foo/`, `/foo` stays `/foo`.
+    Preserve,
+    /// Collapse a trailing slash: `/foo/` becomes `/foo` (the root `/` is left alone).
+    Strip,
+    /// Ensure a trailing slash: `/foo` becomes `/foo/`.
+    ForceDirectory,
+}
+
+fn apply_trailing_slash(path: String, policy: TrailingSlash) -> String {
+    if path.is_empty() {
+        return path;
+    }
+    match policy {
+        TrailingSlash::Preserve => path,
+        TrailingSlash::Strip => {
+            if path.len() > 1 && path.ends_with('/') {
+                path.trim_end_matches('/').to_owned()
+            } else {
+                path
+            }
+        }
+        TrailingSlash::ForceDirectory => {
+            if path.ends_with('/') {
+                path
+            } else {
+                format!("{}/", path)
+            }
+        }
+    }
+}
+
+/// Controls how repeated and ordered query parameters are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryPolicy {
+    /// Sort by key (stable), keeping every occurrence of repeated keys.
+    SortKeysPreserveDuplicates,
+    /// Sort by key, then by value, for a fully deterministic order among
+    /// repeated keys.
+    SortByKeyThenValue,
+    /// Keep the original query order; only removal filters are applied.
+    PreserveOrder,
+}
+
+fn apply_query_policy(mut params: Vec<(String, String)>, policy: QueryPolicy) -> Vec<(String, String)> {
+    match policy {
+        QueryPolicy::SortKeysPreserveDuplicates => {
+            params.sort_by(|a, b| a.0.cmp(&b.0));
+            params
+        }
+        QueryPolicy::SortByKeyThenValue => {
+            params.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+            params
+        }
+        QueryPolicy::PreserveOrder => params,
+    }
+}
+
+/// A predicate for deciding whether a query parameter should be dropped
+/// during normalization, keyed on both the parameter's key and value.
+/// Implemented for any `FnMut(&str, &str) -> bool` closure and for
+/// [`RegexParamFilter`].
+pub trait ParamFilter {
+    fn should_remove(&mut self, key: &str, value: &str) -> bool;
+}
+
+impl<F> ParamFilter for F
+where
+    F: FnMut(&str, &str) -> bool,
+{
+    fn should_remove(&mut self, key: &str, value: &str) -> bool {
+        self(key, value)
+    }
+}
+
+/// A [`ParamFilter`] backed by a set of pre-compiled regexes matched against
+/// the parameter key, same semantics as the `&[&str]` argument to
+/// [`UrlNormalizer::normalize`]. Compiling the patterns once up front (via
+/// [`RegexParamFilter::new`]) avoids paying `Regex::new` on every call for
+/// callers that reuse the same ruleset across many URLs.
+pub struct RegexParamFilter {
+    rules: Vec<Regex>,
+}
+
+impl RegexParamFilter {
+    pub fn new(patterns: &[&str]) -> Result<Self, NormalizeError> {
+        let mut rules = Vec::with_capacity(patterns.len());
+        for &pattern in patterns {
+            rules.push(Regex::new(pattern).map_err(|_| NormalizeError::RegexParseError(pattern.to_owned()))?);
+        }
+        Ok(Self { rules })
+    }
+}
+
+impl ParamFilter for RegexParamFilter {
+    fn should_remove(&mut self, key: &str, _value: &str) -> bool {
+        self.rules.iter().any(|rule| rule.is_match(key))
+    }
+}
+
 pub struct UrlNormalizer {
-    url: Url
+    url: Url,
+    preserve_fragment: bool,
+    preserve_userinfo: bool,
+    trailing_slash: TrailingSlash,
+    query_policy: QueryPolicy,
 }
 
 impl UrlNormalizer {
     pub fn new(tainted_url: &str) -> Result<Self, NormalizeError> {
         let url = Url::parse(tainted_url.trim()).map_err(|_| NormalizeError::UrlParseError)?;
         Ok(Self {
-            url
+            url,
+            preserve_fragment: true,
+            preserve_userinfo: true,
+            trailing_slash: TrailingSlash::Preserve,
+            query_policy: QueryPolicy::SortKeysPreserveDuplicates,
         })
     }
 
-    /// Normalizes URL
+    /// Controls whether `normalize()` keeps the URL's fragment (`#frag`).
+    /// Defaults to `true`; set to `false` for the stricter canonical form
+    /// used by callers that treat the fragment as client-side-only.
+    pub fn preserve_fragment(mut self, preserve: bool) -> Self {
+        self.preserve_fragment = preserve;
+        self
+    }
+
+    /// Controls whether `normalize()` keeps userinfo (`user:pw@`).
+    /// Defaults to `true`; set to `false` to strip credentials from the
+    /// normalized form.
+    pub fn preserve_userinfo(mut self, preserve: bool) -> Self {
+        self.preserve_userinfo = preserve;
+        self
+    }
+
+    /// Sets the trailing-slash policy applied to the path during `normalize()`.
+    /// Defaults to [`TrailingSlash::Preserve`].
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Sets the query-parameter ordering policy applied during
+    /// `normalize()`. Defaults to [`QueryPolicy::SortKeysPreserveDuplicates`].
+    pub fn query_policy(mut self, policy: QueryPolicy) -> Self {
+        self.query_policy = policy;
+        self
+    }
+
+    /// Returns whether the (unnormalized) URL's path currently ends in `/`.
+    pub fn has_trailing_slash(&self) -> bool {
+        self.url.path().ends_with('/')
+    }
+
+    /// Returns whether the URL's path already matches the configured
+    /// [`TrailingSlash`] policy. Only the trailing slash is checked: a `true`
+    /// result does not mean `normalize()` leaves the path's dot segments or
+    /// percent-encoding unchanged (under `Preserve` it is always `true`).
+    pub fn is_normalized(&self) -> bool {
+        match self.trailing_slash {
+            TrailingSlash::Preserve => true,
+            TrailingSlash::Strip => !self.has_trailing_slash() || self.url.path() == "/",
+            TrailingSlash::ForceDirectory => self.has_trailing_slash(),
+        }
+    }
+
+    /// Normalizes URL, dropping query parameters whose key matches any of
+    /// `remove_param_regex`. Compiles the patterns on every call; callers
+    /// normalizing many URLs against the same ruleset should prefer
+    /// [`Self::normalize_with_filter`] with a reusable [`RegexParamFilter`].
     pub fn normalize(&self, remove_param_regex: Option<&[&str]>) -> Result<String, NormalizeError> {
+        match remove_param_regex {
+            Some(patterns) => self.normalize_with_filter(Some(&mut RegexParamFilter::new(patterns)?)),
+            None => self.normalize_with_filter(None),
+        }
+    }
+
+    /// Normalizes URL, dropping query parameters for which `filter` (judging
+    /// by both key and value) returns `true`.
+    pub fn normalize_with_filter(&self, filter: Option<&mut dyn ParamFilter>) -> Result<String, NormalizeError> {
         let url = self.normalize_url()?;
         let mut normalized_path = Vec::<u8>::new();
         let urls = url.path().split("/").collect::<Vec<&str>>();
         for (i, u) in urls.iter().enumerate() {
-            normalized_path.extend_from_slice(encode(u).as_bytes());
+            normalized_path.extend_from_slice(normalize_component(u, &EncodeSet::Path).as_bytes());
             if i < urls.len() - 1 {
                 normalized_path.push(b'/');
             }
         }
         let normalized_path = String::from_utf8(normalized_path).map_err(|_| NormalizeError::UrlEncodeError)?;
-        let params = Self::create_parameter_map(url.query(), remove_param_regex)?;
-        Ok(Self::to_normalized_url(&url, params, normalized_path))
+        let normalized_path = apply_trailing_slash(normalized_path, self.trailing_slash);
+        let params = Self::create_parameter_map(url.query(), filter)?;
+        let params = apply_query_policy(params, self.query_policy);
+        Self::to_normalized_url(&url, params, normalized_path, self.preserve_fragment, self.preserve_userinfo)
     }
 
-    fn to_normalized_url(url: &Url, params: BTreeMap<String, String>, normalized_path: String) -> String {
-        let host = if let Some(h) = url.host_str() {
-            h
-        } else {
-            ""
-        };
+    /// Returns the default port for schemes this crate knows how to normalize,
+    /// mirroring the table rust-url uses internally (RFC 3986 §6.2.3).
+    fn default_port_for_scheme(scheme: &str) -> Option<u16> {
+        match scheme {
+            "http" | "ws" => Some(80),
+            "https" | "wss" => Some(443),
+            "ftp" => Some(21),
+            "gopher" => Some(70),
+            _ => None,
+        }
+    }
+
+    /// IDNA (UTS #46) `ToASCII` normalization for registered names: Unicode
+    /// hosts canonicalize to their `xn--` punycode form, ASCII hosts are
+    /// lowercased.
+    fn normalize_domain(domain: &str) -> Result<String, NormalizeError> {
+        if domain.is_ascii() {
+            return Ok(domain.to_ascii_lowercase());
+        }
+        idna::domain_to_ascii(domain).map_err(|_| NormalizeError::HostParseError(domain.to_owned()))
+    }
+
+    /// Normalizes the URL's host: IDNA for registered names, and URL syntax
+    /// for IP literals. `url::Url` only classifies a host as `Host::Ipv4` /
+    /// `Host::Ipv6` for special schemes (http/https/ws/wss/ftp/file), where
+    /// it has already parsed and canonicalized the address for us; for other
+    /// schemes a numeric-looking host stays a `Host::Domain` and is only
+    /// lowercased by [`Self::normalize_domain`].
+    fn normalize_host(url: &Url) -> Result<String, NormalizeError> {
+        match url.host() {
+            None => Ok("".to_owned()),
+            Some(Host::Domain(domain)) => Self::normalize_domain(domain),
+            Some(Host::Ipv4(addr)) => Ok(addr.to_string()),
+            // `Ipv6Addr`'s `Display` impl already follows RFC 5952 (lowercase
+            // hex, longest-run `::` compression); URL syntax additionally
+            // requires the literal to be bracketed.
+            Some(Host::Ipv6(addr)) => Ok(format!("[{}]", addr)),
+        }
+    }
+
+    fn to_normalized_url(
+        url: &Url,
+        params: Vec<(String, String)>,
+        normalized_path: String,
+        preserve_fragment: bool,
+        preserve_userinfo: bool,
+    ) -> Result<String, NormalizeError> {
+        let scheme = url.scheme().to_ascii_lowercase();
+        let host = Self::normalize_host(url)?;
         let port = if let Some(p) = url.port() {
-            if p == 80 {
+            if Some(p) == Self::default_port_for_scheme(&scheme) {
                 "".to_owned()
             } else {
                 format!(":{}", p)
@@ -71,15 +358,34 @@ impl UrlNormalizer {
         } else {
             "".to_owned()
         };
+        let userinfo = if preserve_userinfo && (!url.username().is_empty() || url.password().is_some()) {
+            let username = normalize_component(url.username(), &EncodeSet::Userinfo);
+            match url.password() {
+                Some(password) => format!("{}:{}@", username, normalize_component(password, &EncodeSet::Userinfo)),
+                None => format!("{}@", username),
+            }
+        } else {
+            "".to_owned()
+        };
         let mut query_string = Vec::new();
         for p in params.iter() {
-            query_string.push(format!("{}={}", p.0, p.1));
+            let key = normalize_component(&p.0, &EncodeSet::Query);
+            let value = normalize_component(&p.1, &EncodeSet::Query);
+            query_string.push(format!("{}={}", key, value));
         }
         let mut query_string_result = query_string.join("&");
         if !query_string.is_empty() {
             query_string_result = format!("?{}", query_string_result);
         }
-        format!("{}://{}{}{}{}", url.scheme(), host, port, normalized_path, query_string_result)
+        let fragment_result = if preserve_fragment {
+            match url.fragment() {
+                Some(frag) => format!("#{}", normalize_component(frag, &EncodeSet::Fragment)),
+                None => "".to_owned(),
+            }
+        } else {
+            "".to_owned()
+        };
+        Ok(format!("{}://{}{}{}{}{}{}", scheme, userinfo, host, port, normalized_path, query_string_result, fragment_result))
     }
 
     fn split_token(pair: &str, tokens: Vec<String>) -> Option<(String, String)> {
@@ -99,8 +405,8 @@ impl UrlNormalizer {
         }
     }
 
-    fn create_parameter_map(query: Option<&str>, remove_param_regex: Option<&[&str]>) -> Result<BTreeMap<String, String>, NormalizeError> {
-        let mut params: BTreeMap<String, String> = BTreeMap::new();
+    fn create_parameter_map(query: Option<&str>, mut filter: Option<&mut dyn ParamFilter>) -> Result<Vec<(String, String)>, NormalizeError> {
+        let mut params: Vec<(String, String)> = Vec::new();
         let query_string = match query {
             Some(q) => {
                 q
@@ -109,15 +415,8 @@ impl UrlNormalizer {
                 return Ok(params);
             }
         };
-        let mut remove_rules = Vec::new();
-        if let Some(remove_param_regex) = remove_param_regex {
-            for &r in remove_param_regex {
-                let regex = Regex::new(r).map_err(|_| NormalizeError::RegexParseError(r.to_owned()))?;
-                remove_rules.push(regex);
-            }
-        };
         let pairs = query_string.split("&");
-        'pair: for pair in pairs {
+        for pair in pairs {
             if pair.len() < 1 {
                 continue;
             }
@@ -126,15 +425,14 @@ impl UrlNormalizer {
                     decode(t)
                 })
                 .take_while(|t| t.is_ok())
-                .map(|t| t.unwrap()).collect::<Vec<String>>();
+                .map(|t| t.unwrap().into_owned()).collect::<Vec<String>>();
 
             if let Some(token) = Self::split_token(pair, token) {
-                for regex in &remove_rules {
-                    if regex.is_match(&token.0) {
-                        continue 'pair;
-                    }
+                let should_remove = filter.as_mut().is_some_and(|f| f.should_remove(&token.0, &token.1));
+                if should_remove {
+                    continue;
                 }
-                params.insert(token.0, token.1);
+                params.push((token.0, token.1));
             }
         }
         Ok(params)